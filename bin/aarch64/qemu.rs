@@ -7,12 +7,23 @@ extern crate alloc;
 
 use core::{
     arch::global_asm,
-    cell::UnsafeCell,
     fmt::{self, Write},
 };
-use log::{self, info, LevelFilter, Log, Metadata, Record};
+use log::{info, LevelFilter};
 
+use caliga_bootloader::allocator::{init_heap, no_alloc, FreeListAllocator, Guarded};
+use caliga_bootloader::console::{RingBuffer, RingBufferWriter};
 use caliga_bootloader::io::{io::Io, mmio::Mmio};
+use caliga_bootloader::log::{init_logger, ConsoleLogger};
+use caliga_bootloader::panic::report;
+
+/// Capacity, in bytes, of the early-boot log ring buffer.
+const EARLY_LOG_CAPACITY: usize = 4096;
+
+/// Captures log output from the very first instruction, before UART0 has
+/// been initialized (or while it's too slow/busy to write to immediately),
+/// and without ever blocking or faulting.
+static EARLY_LOG: RingBuffer<EARLY_LOG_CAPACITY> = RingBuffer::new();
 
 // The start procedure
 global_asm!(include_str!("start.S"));
@@ -20,25 +31,18 @@ global_asm!(include_str!("start.S"));
 /// Address of UART0 on default QEMU for aarch64
 pub const UART0_ADDR: usize = 0x0900_0000;
 
-// An unimplemented allocator to see how it may be structured
-//mod bump_allocator {
-use core::alloc::{GlobalAlloc, Layout};
-
 #[global_allocator]
-static GLOBAL_ALLOCATOR: Aarch64QemuAlloc = Aarch64QemuAlloc {};
-
-struct Aarch64QemuAlloc;
-
-unsafe impl GlobalAlloc for Aarch64QemuAlloc {
-    unsafe fn alloc(&self, _layout: Layout) -> *mut u8 {
-        panic!("Allocation is unimplemented!");
-    }
-
-    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
-        unimplemented!();
-    }
+static GLOBAL_ALLOCATOR: Guarded<FreeListAllocator> = Guarded::new(FreeListAllocator::empty());
+
+// Bounds of the heap region, exported by the linker script (start.S / the
+// linker symbols it places the bootloader's BSS/heap after). Both are
+// zero-sized linker symbols whose *address* encodes the value (the usual
+// idiom for `start`/`size`-style linker-defined constants), so both must be
+// read with `&symbol as *const _ as usize`, never dereferenced.
+extern "C" {
+    static __heap_start: u8;
+    static __heap_size: u8;
 }
-//}
 
 #[repr(packed)]
 pub struct Pl011Uart {
@@ -74,90 +78,62 @@ impl Write for Pl011Uart {
     }
 }
 
-/// A logger that outputs to a PL011 UART
-///
-/// This is a proof of concept to see what is necessary to set up a default logger.
-///
-/// # Interior Mutability
-///
-/// Internally, it uses an [`UnsafeCell`] to contain the UART struct because the method `log` would disallow
-/// interior mutability, otherwise. Since this bootloader will always run on a single thread, there should be
-/// no problems with race conditions.
-struct UartPl011Logger {
-    uart: UnsafeCell<&'static mut Pl011Uart>,
-}
-
-// Implement traits that are needed for `Log`
-unsafe impl Sync for UartPl011Logger {}
-unsafe impl Send for UartPl011Logger {}
-
-impl Log for UartPl011Logger {
-    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
-        metadata.level().to_level_filter() <= log::max_level()
-    }
-
-    // A very basic logger. Only outputs the log if it's possible without any allocations
-    //
-    // I want to move this into a cross-architecture implementation so that all logs can be formatted
-    // the same. Also, it might be useful to use this in the panic logs, too.
-    //
-    // TODO: Deal with all the calls to `unwrap`
-    fn log(&self, record: &Record<'_>) {
-        // Get a mutable reference to the UART
-        let uart = unsafe { &mut *self.uart.get() };
-
-        // Write log level
-        write!(uart, "[{}] ", record.level().as_str()).unwrap();
-
-        // Try to write log without any allocations
-        if let Some(args) = record.args().as_str() {
-            uart.write_str(args).unwrap();
-        } else {
-            uart.write_str("Could not get log; allocator needed")
-                .unwrap();
-        }
-
-        // Try to write log file and line without any allocations
-        if let (Some(file_name), Some(line)) = (record.file(), record.line()) {
-            write!(uart, ", {}:{:?}", file_name, line).unwrap();
-        }
+#[panic_handler]
+fn handle_panic(info: &core::panic::PanicInfo) -> ! {
+    // Panicking must never allocate; catch it at the source rather than
+    // letting it manifest as a fault.
+    let _guard = no_alloc();
 
-        uart.write_char('\n').unwrap();
+    // Re-initialize UART0 and flush whatever was logged before it was ready,
+    // so the last pre-panic messages make it out first. If any UART write
+    // fails, fall straight through to the halt instead of unwrapping.
+    let uart = unsafe { Pl011Uart::new(UART0_ADDR) };
+    if EARLY_LOG.reader().flush(uart).is_ok() {
+        report(info, uart).ok();
     }
 
-    fn flush(&self) {}
+    halt()
 }
 
-#[panic_handler]
-fn handle_panic(info: &core::panic::PanicInfo) -> ! {
-    // Re-initialize UART0 and print a panic log
-    let uart = unsafe { Pl011Uart::new(UART0_ADDR) };
-    // TODO: Maybe halt if this returns an error
-    writeln!(uart, "[PANIC] {}", info).unwrap();
-    loop {}
+/// Parks the core in a low-power state instead of busy-spinning, since
+/// nothing will wake a panicked core back up to do useful work.
+fn halt() -> ! {
+    loop {
+        unsafe { core::arch::asm!("wfe") };
+    }
 }
 
-// The default logger
-static mut LOGGER: Option<UartPl011Logger> = None;
+// The default logger, backed by the early-boot ring buffer so that logging
+// never blocks on (or depends on) UART0 being ready
+static mut LOGGER: Option<ConsoleLogger<RingBufferWriter<EARLY_LOG_CAPACITY>>> = None;
 
 #[no_mangle]
 #[link_section = ".text.boot"]
 pub unsafe extern "C" fn qemu_entry() {
-    // Initialize UART0
-    // The only other place it should be initialized is during a panic for emergency serial output
-    let uart = unsafe { Pl011Uart::new(UART0_ADDR) };
-
-    // Initialize logger using UART0
+    // Initialize the logger using the early-boot ring buffer
     let logger = {
-        LOGGER = Some(UartPl011Logger { uart: uart.into() });
+        LOGGER = Some(ConsoleLogger::new(EARLY_LOG.writer()));
         LOGGER.as_ref().unwrap()
     };
-    log::set_logger(logger).unwrap();
-    log::set_max_level(LevelFilter::Debug);
+    init_logger(logger, LevelFilter::Debug).unwrap();
+
+    // Initialize the heap before constructing any `Box`
+    unsafe {
+        init_heap(
+            GLOBAL_ALLOCATOR.inner(),
+            &__heap_start as *const u8 as usize,
+            &__heap_size as *const u8 as usize,
+        )
+    };
 
     // Test out logger
     info!("Done with info log");
 
+    // Initialize UART0 and flush everything logged so far to it
+    // The only other place it should be initialized is during a panic for emergency serial output
+    let uart = unsafe { Pl011Uart::new(UART0_ADDR) };
+    EARLY_LOG.reader().flush(uart).ok();
+
     // TODO: Run kernel
     panic!("End of bootloader reached");
 }
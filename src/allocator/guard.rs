@@ -0,0 +1,77 @@
+//! Enforcement for no-alloc critical sections.
+//!
+//! Some code paths (the logger, the panic handler, anything that runs before
+//! [`super::init_heap`]) must never allocate, but nothing stopped a future
+//! change from silently introducing a heap call that would fault. [`no_alloc`]
+//! marks a region as forbidden for the lifetime of its guard; any allocation
+//! attempted through [`Guarded`] while one is alive panics at the call site
+//! instead of manifesting as an opaque fault later on.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::UnsafeCell;
+
+/// Depth of nested [`no_alloc`] guards currently alive.
+///
+/// # Interior Mutability
+///
+/// Uses an [`UnsafeCell`] rather than an atomic because the bootloader runs
+/// on a single thread; there are no real race conditions to guard against.
+struct ForbidDepth(UnsafeCell<usize>);
+
+unsafe impl Sync for ForbidDepth {}
+
+static FORBID_DEPTH: ForbidDepth = ForbidDepth(UnsafeCell::new(0));
+
+/// RAII guard returned by [`no_alloc`]. Allocation is forbidden for as long
+/// as it, or any other live guard, exists.
+pub struct NoAllocGuard(());
+
+impl Drop for NoAllocGuard {
+    fn drop(&mut self) {
+        unsafe { *FORBID_DEPTH.0.get() -= 1 };
+    }
+}
+
+/// Enters a no-allocation critical section for the lifetime of the returned
+/// guard. Any call into [`Guarded`]'s `alloc`/`dealloc` while the guard is
+/// alive panics.
+pub fn no_alloc() -> NoAllocGuard {
+    unsafe { *FORBID_DEPTH.0.get() += 1 };
+    NoAllocGuard(())
+}
+
+fn assert_allowed(operation: &str) {
+    if unsafe { *FORBID_DEPTH.0.get() } > 0 {
+        panic!("{operation} attempted inside a no_alloc() critical section");
+    }
+}
+
+/// Wraps a [`GlobalAlloc`] implementation so that it panics instead of
+/// allocating while a [`no_alloc`] guard is alive.
+pub struct Guarded<A> {
+    inner: A,
+}
+
+impl<A> Guarded<A> {
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+
+    /// Returns the wrapped allocator, e.g. to call setup methods (like
+    /// [`super::init_heap`]) that aren't part of [`GlobalAlloc`] itself.
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for Guarded<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        assert_allowed("alloc");
+        unsafe { self.inner.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        assert_allowed("dealloc");
+        unsafe { self.inner.dealloc(ptr, layout) }
+    }
+}
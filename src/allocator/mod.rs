@@ -0,0 +1,253 @@
+//! A heap subsystem for the bootloader.
+//!
+//! [`FreeListAllocator`] manages a single region of memory described by a
+//! `start`/`size` pair (typically bounds exported by the linker script).
+//! [`FreeListAllocator::init`] seeds the list with one free block spanning
+//! the whole region; from then on, `alloc` carves pieces off free blocks and
+//! `dealloc` returns them, coalescing with whichever neighboring free blocks
+//! happen to be adjacent.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::UnsafeCell;
+use core::mem;
+use core::ptr::{self, NonNull};
+
+pub mod guard;
+pub use guard::{no_alloc, Guarded, NoAllocGuard};
+
+/// A node in the free list.
+///
+/// Lives inline at the start of the free region it describes; `size`
+/// includes this header.
+struct FreeBlock {
+    size: usize,
+    next: Option<NonNull<FreeBlock>>,
+}
+
+impl FreeBlock {
+    fn addr(&self) -> usize {
+        self as *const _ as usize
+    }
+
+    fn end_addr(&self) -> usize {
+        self.addr() + self.size
+    }
+}
+
+/// A free-list [`GlobalAlloc`] over a single region of memory.
+///
+/// # Safety
+///
+/// [`FreeListAllocator::init`] must be called exactly once, before the first
+/// allocation, with a region that isn't otherwise in use.
+pub struct FreeListAllocator {
+    head: UnsafeCell<Option<NonNull<FreeBlock>>>,
+}
+
+unsafe impl Sync for FreeListAllocator {}
+
+impl FreeListAllocator {
+    /// Creates an allocator with no backing memory. [`init_heap`] must be
+    /// called before the first allocation.
+    pub const fn empty() -> Self {
+        Self {
+            head: UnsafeCell::new(None),
+        }
+    }
+
+    /// Initializes the heap to span `[start, start + size)`.
+    ///
+    /// # Safety
+    ///
+    /// `start` must be valid for `size` bytes, and that region must not
+    /// overlap anything else in use. This must be called before any
+    /// allocation is made through this allocator.
+    unsafe fn init(&self, start: usize, size: usize) {
+        unsafe { self.add_free_region(start, size) };
+    }
+
+    /// Adds `[addr, addr + size)` to the free list, merging it with whichever
+    /// of its immediate neighbors (by address) happen to already be free.
+    ///
+    /// # Safety
+    ///
+    /// `[addr, addr + size)` must be valid, unused memory.
+    unsafe fn add_free_region(&self, addr: usize, size: usize) {
+        if size < mem::size_of::<FreeBlock>() {
+            // Too small to ever host an allocation or its own free-list node.
+            return;
+        }
+
+        // Walk the list to find `prev`/`next`, keeping it sorted by address so
+        // that adjacent regions are easy to spot and merge.
+        let mut prev: Option<NonNull<FreeBlock>> = None;
+        let mut next = unsafe { *self.head.get() };
+        while let Some(node) = next {
+            let node_ref = unsafe { node.as_ref() };
+            if node_ref.addr() >= addr {
+                break;
+            }
+            prev = Some(node);
+            next = node_ref.next;
+        }
+
+        let mut new_size = size;
+        let mut new_next = next;
+        if let Some(next_node) = next {
+            let next_ref = unsafe { next_node.as_ref() };
+            if next_ref.addr() == addr + new_size {
+                new_size += next_ref.size;
+                new_next = next_ref.next;
+            }
+        }
+
+        if let Some(mut prev_node) = prev {
+            let prev_block = unsafe { prev_node.as_mut() };
+            if prev_block.end_addr() == addr {
+                // Fully absorbed into the previous block; no new node needed.
+                prev_block.size += new_size;
+                prev_block.next = new_next;
+                return;
+            }
+        }
+
+        let block_ptr = addr as *mut FreeBlock;
+        unsafe {
+            block_ptr.write(FreeBlock {
+                size: new_size,
+                next: new_next,
+            });
+        }
+        let block = NonNull::new(block_ptr);
+
+        match prev {
+            Some(mut prev_node) => unsafe { prev_node.as_mut().next = block },
+            None => unsafe { *self.head.get() = block },
+        }
+    }
+
+    /// Finds the first free block that can satisfy `size`/`align`, removes it
+    /// from the list, and returns `(block_start, alloc_start, block_end)` so
+    /// the caller can return both the leading alignment padding and any
+    /// trailing leftover space to the list.
+    unsafe fn find_region(&self, size: usize, align: usize) -> Option<(usize, usize, usize)> {
+        let mut prev_link = self.head.get();
+        let mut cursor = unsafe { *prev_link };
+
+        while let Some(mut node) = cursor {
+            let block = unsafe { node.as_mut() };
+            if let Some(alloc_start) = Self::alloc_from_region(block, size, align) {
+                let block_addr = block.addr();
+                let region_end = block.end_addr();
+                unsafe { *prev_link = block.next };
+                return Some((block_addr, alloc_start, region_end));
+            }
+
+            prev_link = unsafe { &mut node.as_mut().next };
+            cursor = unsafe { *prev_link };
+        }
+
+        None
+    }
+
+    /// Returns the address to allocate `size` bytes aligned to `align` out of
+    /// `block`, if it fits. Rejects the block if the alignment padding would
+    /// leave a leading or trailing sliver too small to become its own
+    /// free-list node, since that sliver would otherwise be leaked.
+    fn alloc_from_region(block: &FreeBlock, size: usize, align: usize) -> Option<usize> {
+        let alloc_start = align_up(block.addr(), align)?;
+        let alloc_end = alloc_start.checked_add(size)?;
+        if alloc_end > block.end_addr() {
+            return None;
+        }
+
+        let front_gap = alloc_start - block.addr();
+        if front_gap > 0 && front_gap < mem::size_of::<FreeBlock>() {
+            return None;
+        }
+
+        let excess_size = block.end_addr() - alloc_end;
+        if excess_size > 0 && excess_size < mem::size_of::<FreeBlock>() {
+            return None;
+        }
+
+        Some(alloc_start)
+    }
+
+    /// Rounds `layout` up to something that can host a [`FreeBlock`] header
+    /// once freed, guarding against overflow throughout.
+    fn adjusted_layout(layout: Layout) -> Option<Layout> {
+        let align = layout.align().max(mem::align_of::<FreeBlock>());
+        let layout = Layout::from_size_align(layout.size(), align).ok()?;
+        let layout = layout.pad_to_align();
+        if layout.size() < mem::size_of::<FreeBlock>() {
+            Layout::from_size_align(mem::size_of::<FreeBlock>(), layout.align()).ok()
+        } else {
+            Some(layout)
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for FreeListAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let Some(layout) = Self::adjusted_layout(layout) else {
+            return ptr::null_mut();
+        };
+
+        let Some((block_start, alloc_start, region_end)) =
+            (unsafe { self.find_region(layout.size(), layout.align()) })
+        else {
+            return ptr::null_mut();
+        };
+
+        // Return the leading alignment padding, if any, to the free list
+        // instead of leaking it.
+        let front_gap = alloc_start - block_start;
+        if front_gap > 0 {
+            unsafe { self.add_free_region(block_start, front_gap) };
+        }
+
+        // `find_region` already checked this add doesn't overflow.
+        let alloc_end = alloc_start + layout.size();
+        let excess_size = region_end - alloc_end;
+        if excess_size > 0 {
+            unsafe { self.add_free_region(alloc_end, excess_size) };
+        }
+
+        alloc_start as *mut u8
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let Some(layout) = Self::adjusted_layout(layout) else {
+            return;
+        };
+        unsafe { self.add_free_region(ptr as usize, layout.size()) };
+    }
+}
+
+/// Rounds `addr` up to the nearest multiple of `align`, returning `None`
+/// rather than wrapping on overflow.
+///
+/// `align` must be a power of two, as guaranteed by [`Layout`].
+fn align_up(addr: usize, align: usize) -> Option<usize> {
+    let remainder = addr & (align - 1);
+    if remainder == 0 {
+        Some(addr)
+    } else {
+        addr.checked_add(align - remainder)
+    }
+}
+
+/// Initializes `allocator`'s heap to span `[start, start + size)`.
+///
+/// Each architecture owns the static storage for its [`FreeListAllocator`]
+/// (the `#[global_allocator]`) and calls this once, before any `Box` or other
+/// allocation, typically with bounds exported by the linker script.
+///
+/// # Safety
+///
+/// `start` must be valid for `size` bytes, and that region must not overlap
+/// anything else in use.
+pub unsafe fn init_heap(allocator: &FreeListAllocator, start: usize, size: usize) {
+    unsafe { allocator.init(start, size) };
+}
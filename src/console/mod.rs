@@ -0,0 +1,127 @@
+//! A non-blocking ring-buffer console.
+//!
+//! [`RingBuffer`] captures bytes written to it from the very first instruction,
+//! before a real console device (UART, filesystem, ...) is ready to receive
+//! them, and without ever blocking or faulting: writes that arrive while the
+//! buffer is full are silently dropped. Once a real device is ready, its
+//! contents can be drained with [`RingBufferReader::flush`].
+
+use core::cell::UnsafeCell;
+use core::fmt::{self, Write};
+
+/// A fixed-capacity, single-producer/single-consumer byte ring buffer.
+///
+/// # Interior Mutability
+///
+/// Like [`crate::log::ConsoleLogger`], this uses an [`UnsafeCell`] so that it
+/// can be written to through a shared `&'static` reference. The bootloader
+/// runs on a single thread, so there are no real race conditions between the
+/// writer and reader halves handed out by [`RingBuffer::split`].
+pub struct RingBuffer<const N: usize> {
+    buf: UnsafeCell<[u8; N]>,
+    write_pos: UnsafeCell<usize>,
+    read_pos: UnsafeCell<usize>,
+    len: UnsafeCell<usize>,
+}
+
+unsafe impl<const N: usize> Sync for RingBuffer<N> {}
+unsafe impl<const N: usize> Send for RingBuffer<N> {}
+
+impl<const N: usize> RingBuffer<N> {
+    /// Creates an empty ring buffer.
+    pub const fn new() -> Self {
+        Self {
+            buf: UnsafeCell::new([0; N]),
+            write_pos: UnsafeCell::new(0),
+            read_pos: UnsafeCell::new(0),
+            len: UnsafeCell::new(0),
+        }
+    }
+
+    /// Splits `self` into a writer half and a reader half.
+    ///
+    /// `self` is expected to be a `'static` ring buffer (typically declared as
+    /// a `static`), so both halves can be handed to independent parts of the
+    /// bootloader (e.g. the logger and the panic handler).
+    pub fn split(&'static self) -> (RingBufferWriter<N>, RingBufferReader<N>) {
+        (self.writer(), self.reader())
+    }
+
+    /// Returns a writer handle for this buffer. Can be called more than once;
+    /// every handle writes into the same underlying buffer.
+    pub fn writer(&'static self) -> RingBufferWriter<N> {
+        RingBufferWriter { ring: self }
+    }
+
+    /// Returns a reader handle for this buffer. Can be called more than once;
+    /// every handle drains the same underlying buffer.
+    pub fn reader(&'static self) -> RingBufferReader<N> {
+        RingBufferReader { ring: self }
+    }
+
+    /// Pushes `byte` into the buffer, silently dropping it if the buffer is
+    /// full. Logging must never back up or fault, so this never blocks.
+    fn push(&self, byte: u8) {
+        let len = unsafe { &mut *self.len.get() };
+        if *len == N {
+            return;
+        }
+
+        let write_pos = unsafe { &mut *self.write_pos.get() };
+        let buf = unsafe { &mut *self.buf.get() };
+        buf[*write_pos] = byte;
+        *write_pos = (*write_pos + 1) % N;
+        *len += 1;
+    }
+
+    /// Pops the oldest buffered byte, if any.
+    fn pop(&self) -> Option<u8> {
+        let len = unsafe { &mut *self.len.get() };
+        if *len == 0 {
+            return None;
+        }
+
+        let read_pos = unsafe { &mut *self.read_pos.get() };
+        let buf = unsafe { &*self.buf.get() };
+        let byte = buf[*read_pos];
+        *read_pos = (*read_pos + 1) % N;
+        *len -= 1;
+        Some(byte)
+    }
+}
+
+/// The writer half of a [`RingBuffer`].
+///
+/// Implements [`core::fmt::Write`] so it can back a
+/// [`crate::log::ConsoleLogger`] directly.
+pub struct RingBufferWriter<const N: usize> {
+    ring: &'static RingBuffer<N>,
+}
+
+impl<const N: usize> Write for RingBufferWriter<N> {
+    fn write_str(&mut self, out_string: &str) -> fmt::Result {
+        for out_byte in out_string.bytes() {
+            self.ring.push(out_byte);
+        }
+        Ok(())
+    }
+}
+
+/// The reader half of a [`RingBuffer`].
+pub struct RingBufferReader<const N: usize> {
+    ring: &'static RingBuffer<N>,
+}
+
+impl<const N: usize> RingBufferReader<N> {
+    /// Drains every currently-buffered byte into `console`.
+    ///
+    /// Intended to be called once a real console device is ready (e.g. right
+    /// after UART initialization, or from the panic handler) to replay
+    /// everything logged before that point.
+    pub fn flush<W: Write + ?Sized>(&self, console: &mut W) -> fmt::Result {
+        while let Some(byte) = self.ring.pop() {
+            console.write_char(byte as char)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+#![no_std]
+
+extern crate alloc;
+
+pub mod allocator;
+pub mod console;
+pub mod filesystem;
+pub mod io;
+pub mod log;
+pub mod panic;
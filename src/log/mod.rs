@@ -0,0 +1,103 @@
+//! A cross-architecture logging subsystem.
+//!
+//! Each architecture supplies its own console device (anything implementing
+//! [`core::fmt::Write`] + [`Sync`]) and wraps it in a [`ConsoleLogger`]. The
+//! formatting itself lives in [`write_log`] so that every target, and the
+//! panic handler, produce identically formatted output.
+
+use core::cell::UnsafeCell;
+use core::fmt::{self, Write};
+
+use log::{Log, Metadata, Record, SetLoggerError};
+
+/// A [`Log`] implementation that writes formatted records to a `T: Write`
+/// console.
+///
+/// # Interior Mutability
+///
+/// Internally, it uses an [`UnsafeCell`] to contain the console because the method `log` would disallow
+/// interior mutability, otherwise. Since this bootloader will always run on a single thread, there should be
+/// no problems with race conditions.
+pub struct ConsoleLogger<T: Write> {
+    console: UnsafeCell<T>,
+}
+
+// Implement traits that are needed for `Log`
+unsafe impl<T: Write + Sync> Sync for ConsoleLogger<T> {}
+unsafe impl<T: Write + Send> Send for ConsoleLogger<T> {}
+
+impl<T: Write> ConsoleLogger<T> {
+    /// Wraps `console` in a logger.
+    pub const fn new(console: T) -> Self {
+        Self {
+            console: UnsafeCell::new(console),
+        }
+    }
+}
+
+impl<T: Write + Sync> Log for ConsoleLogger<T> {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level().to_level_filter() <= log::max_level()
+    }
+
+    // A very basic logger, formatted without any allocations via `write_log`.
+    // A failed console write is ignored; logging never panics on it.
+    fn log(&self, record: &Record<'_>) {
+        // Logging must never allocate; catch it at the source rather than
+        // letting it manifest as a fault before the heap is ready.
+        let _guard = crate::allocator::no_alloc();
+
+        // Get a mutable reference to the console
+        let console = unsafe { &mut *self.console.get() };
+
+        let _ = write_log(
+            console,
+            record.level().as_str(),
+            *record.args(),
+            record.file().zip(record.line()),
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+/// Formats a single log entry and writes it to `console`.
+///
+/// This is shared by [`ConsoleLogger`] and the panic handler so that a log
+/// emitted during normal operation looks identical to one emitted while
+/// panicking. Writing `args` straight through never allocates, even when it
+/// contains formatting placeholders: `fmt::Arguments` is rendered directly
+/// into the `Write` sink.
+pub fn write_log<W: Write + ?Sized>(
+    console: &mut W,
+    level: &str,
+    args: fmt::Arguments<'_>,
+    location: Option<(&str, u32)>,
+) -> fmt::Result {
+    // Write log level
+    write!(console, "[{}] ", level)?;
+
+    write!(console, "{}", args)?;
+
+    // Try to write log file and line without any allocations
+    if let Some((file_name, line)) = location {
+        write!(console, ", {}:{:?}", file_name, line)?;
+    }
+
+    console.write_char('\n')
+}
+
+/// Installs `logger` as the global logger for the `log` crate.
+///
+/// `logger` must be `'static` because [`log::set_logger`] requires it. Each
+/// architecture owns the static storage for its [`ConsoleLogger`] (typically
+/// a `static mut Option<ConsoleLogger<Console>>`, initialized once in the
+/// entry point) and passes a `&'static` reference in here.
+pub fn init_logger<T: Write + Sync>(
+    logger: &'static ConsoleLogger<T>,
+    max_level: log::LevelFilter,
+) -> Result<(), SetLoggerError> {
+    log::set_logger(logger)?;
+    log::set_max_level(max_level);
+    Ok(())
+}
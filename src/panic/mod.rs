@@ -0,0 +1,48 @@
+//! Shared panic-handling support.
+//!
+//! Each architecture keeps its own `#[panic_handler]` (halting is inherently
+//! architecture-specific), but routes through here for everything that isn't:
+//! an optional user-installed hook, and the same log formatting normal
+//! records use.
+
+use core::cell::UnsafeCell;
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+
+use crate::log::write_log;
+
+/// A panic hook, analogous to `std::panic::set_hook`.
+pub type PanicHook = fn(&PanicInfo<'_>);
+
+/// Holds the currently-installed [`PanicHook`], if any.
+///
+/// # Interior Mutability
+///
+/// Uses an [`UnsafeCell`] because the hook is set once from the single boot
+/// thread, well before a panic could occur; there are no race conditions to
+/// guard against here.
+struct HookCell(UnsafeCell<Option<PanicHook>>);
+
+unsafe impl Sync for HookCell {}
+
+static HOOK: HookCell = HookCell(UnsafeCell::new(None));
+
+/// Installs `hook` to run before the default panic output.
+pub fn set_hook(hook: PanicHook) {
+    unsafe { *HOOK.0.get() = Some(hook) };
+}
+
+/// Runs the installed hook (if any) and writes the default `[PANIC]` line to
+/// `console`, using the same formatting normal log records use.
+///
+/// Intended to be called once from each architecture's `#[panic_handler]`,
+/// after flushing any buffered early-boot console so the last pre-panic
+/// messages make it out first. Callers should not `unwrap()` the result:
+/// if the console write fails, fall straight through to halting.
+pub fn report<W: Write + ?Sized>(info: &PanicInfo<'_>, console: &mut W) -> fmt::Result {
+    if let Some(hook) = unsafe { *HOOK.0.get() } {
+        hook(info);
+    }
+
+    write_log(console, "PANIC", format_args!("{}", info), None)
+}